@@ -0,0 +1,144 @@
+//! FAT boot sector (BIOS Parameter Block + extended BPB)
+
+use packing::{Packed, PackedSize};
+
+use crate::config::{Config, FatType, BYTES_PER_SECTOR, NUM_FATS};
+
+const MEDIA_DESCRIPTOR: u8 = 0xF8;
+
+pub const VOLUME_LABEL: [u8; 11] = *b"GHOSTFAT   ";
+
+/// Boot sector, covering the common BPB and the FAT32 extended BPB
+///
+/// The FAT32-only fields (`sectors_per_fat_32`, `root_cluster`, `fs_info_sector`,
+/// `backup_boot_sector`) are left zeroed for FAT12/FAT16 volumes, where the
+/// FAT16-style `sectors_per_fat_16` is used instead.
+#[derive(Packed, PackedSize, Debug, Clone, Copy)]
+#[packed(little_endian, lsb0)]
+pub struct FatBootBlock {
+    #[pkd(7, 0, 0, 0)]
+    pub jump_instruction: [u8; 3],
+    #[pkd(7, 0, 3, 10)]
+    pub oem_info: [u8; 8],
+    #[pkd(7, 0, 11, 12)]
+    pub bytes_per_sector: u16,
+    #[pkd(7, 0, 13, 13)]
+    pub sectors_per_cluster: u8,
+    #[pkd(7, 0, 14, 15)]
+    pub reserved_sectors: u16,
+    #[pkd(7, 0, 16, 16)]
+    pub num_fats: u8,
+    #[pkd(7, 0, 17, 18)]
+    pub root_dir_entries: u16,
+    #[pkd(7, 0, 19, 20)]
+    pub total_sectors_16: u16,
+    #[pkd(7, 0, 21, 21)]
+    pub media_descriptor: u8,
+    #[pkd(7, 0, 22, 23)]
+    pub sectors_per_fat_16: u16,
+    #[pkd(7, 0, 24, 25)]
+    pub sectors_per_track: u16,
+    #[pkd(7, 0, 26, 27)]
+    pub num_heads: u16,
+    #[pkd(7, 0, 28, 31)]
+    pub hidden_sectors: u32,
+    #[pkd(7, 0, 32, 35)]
+    pub total_sectors_32: u32,
+
+    // FAT32 extended BPB
+    #[pkd(7, 0, 36, 39)]
+    pub sectors_per_fat_32: u32,
+    #[pkd(7, 0, 40, 41)]
+    pub ext_flags: u16,
+    #[pkd(7, 0, 42, 43)]
+    pub fs_version: u16,
+    #[pkd(7, 0, 44, 47)]
+    pub root_cluster: u32,
+    #[pkd(7, 0, 48, 49)]
+    pub fs_info_sector: u16,
+    #[pkd(7, 0, 50, 51)]
+    pub backup_boot_sector: u16,
+    #[pkd(7, 0, 52, 63)]
+    pub reserved0: [u8; 12],
+
+    #[pkd(7, 0, 64, 64)]
+    pub drive_number: u8,
+    #[pkd(7, 0, 65, 65)]
+    pub reserved1: u8,
+    #[pkd(7, 0, 66, 66)]
+    pub ext_boot_signature: u8,
+    #[pkd(7, 0, 67, 70)]
+    pub volume_id: u32,
+    #[pkd(7, 0, 71, 81)]
+    pub volume_label: [u8; 11],
+    #[pkd(7, 0, 82, 89)]
+    pub fs_type: [u8; 8],
+    #[pkd(7, 0, 90, 509)]
+    pub boot_code: [u8; 420],
+}
+
+impl FatBootBlock {
+    /// `root_dir_entries` should be the real entry count a [`crate::GhostFat`]
+    /// serves for its registered files (see `GhostFat::root_dir_entries`),
+    /// not a fixed guess -- hosts derive `FirstDataSector` from this field,
+    /// and it must agree with where the cluster region actually starts.
+    pub fn new(config: &Config, root_dir_entries: u32) -> Self {
+        let fat_type = config.fat_type();
+        let is_fat32 = fat_type == FatType::Fat32;
+
+        let mut block = Self {
+            jump_instruction: [0xEB, 0x3C, 0x90],
+            oem_info: *b"GHOSTFAT",
+            bytes_per_sector: BYTES_PER_SECTOR as u16,
+            sectors_per_cluster: config.sectors_per_cluster as u8,
+            reserved_sectors: config.start_fat0() as u16,
+            num_fats: NUM_FATS as u8,
+            root_dir_entries: if is_fat32 { 0 } else { root_dir_entries as u16 },
+            total_sectors_16: if config.num_blocks < 0x10000 {
+                config.num_blocks as u16
+            } else {
+                0
+            },
+            media_descriptor: MEDIA_DESCRIPTOR,
+            sectors_per_fat_16: if is_fat32 { 0 } else { config.sectors_per_fat() as u16 },
+            sectors_per_track: 1,
+            num_heads: 1,
+            hidden_sectors: config.partition_start(),
+            total_sectors_32: if config.num_blocks >= 0x10000 {
+                config.num_blocks
+            } else {
+                0
+            },
+
+            sectors_per_fat_32: 0,
+            ext_flags: 0,
+            fs_version: 0,
+            root_cluster: 0,
+            fs_info_sector: 0,
+            backup_boot_sector: 0,
+            reserved0: [0; 12],
+
+            drive_number: 0x80,
+            reserved1: 0,
+            ext_boot_signature: 0x29,
+            volume_id: 0x0042_0042,
+            volume_label: VOLUME_LABEL,
+            fs_type: *b"FAT     ",
+            boot_code: [0; 420],
+        };
+
+        match fat_type {
+            FatType::Fat12 => block.fs_type = *b"FAT12   ",
+            FatType::Fat16 => block.fs_type = *b"FAT16   ",
+            FatType::Fat32 => {
+                block.sectors_per_fat_32 = config.sectors_per_fat();
+                block.root_cluster = 2;
+                block.fs_info_sector = 1;
+                block.backup_boot_sector = 6;
+                block.fs_type = *b"FAT32   ";
+            }
+        }
+
+        block
+    }
+}
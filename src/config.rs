@@ -0,0 +1,152 @@
+//! Filesystem layout: FAT variant selection and block/sector/cluster bookkeeping
+
+/// Bytes per logical block; GhostFAT only ever speaks 512 byte sectors
+pub(crate) const BYTES_PER_SECTOR: u32 = 512;
+
+/// GhostFAT only ever writes a single FAT copy, even though the spec
+/// recommends two -- hosts tolerate this fine.
+pub(crate) const NUM_FATS: u32 = 1;
+
+/// Root directory entries assumed by [`Config::new`] when estimating the
+/// data-region cluster count (for every FAT type); the root directory a
+/// [`crate::GhostFat`] serves is sized from its registered files instead
+pub(crate) const ROOT_DIR_ENTRIES: u32 = 16;
+
+/// On-disk FAT entry width, selected from the data-region cluster count
+///
+/// Thresholds match the ones real drivers use, see Microsoft's FAT spec
+/// section 3.5: fewer than 4085 clusters is FAT12, fewer than 65525 is
+/// FAT16, otherwise FAT32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    fn from_cluster_count(clusters: u32) -> Self {
+        if clusters < 4085 {
+            FatType::Fat12
+        } else if clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        }
+    }
+
+    /// Number of bits a single FAT entry occupies on disk
+    pub(crate) fn bits_per_entry(&self) -> u32 {
+        match self {
+            FatType::Fat12 => 12,
+            FatType::Fat16 => 16,
+            FatType::Fat32 => 32,
+        }
+    }
+}
+
+/// Layout configuration for a [`crate::GhostFat`] volume
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Total number of 512 byte blocks presented by the device
+    pub num_blocks: u32,
+    /// Blocks per allocation unit
+    pub sectors_per_cluster: u32,
+    /// Blocks reserved before the first FAT, including the boot block
+    pub reserved_sectors: u32,
+    fat_type: FatType,
+    sectors_per_fat: u32,
+    mbr: bool,
+}
+
+impl Config {
+    pub fn new(num_blocks: u32, sectors_per_cluster: u32) -> Self {
+        let mut reserved_sectors = 1;
+
+        // Estimate the data region so we can pick a FAT type the same way
+        // real drivers do, then size the FAT to match it.
+        let root_dir_sectors =
+            (ROOT_DIR_ENTRIES * 32 + BYTES_PER_SECTOR - 1) / BYTES_PER_SECTOR;
+        let estimate_clusters = |reserved_sectors: u32| {
+            let data_sectors = num_blocks.saturating_sub(reserved_sectors + root_dir_sectors);
+            data_sectors / sectors_per_cluster
+        };
+
+        let mut cluster_count = estimate_clusters(reserved_sectors);
+        let mut fat_type = FatType::from_cluster_count(cluster_count);
+
+        // FAT32 reserves sectors ahead of the FAT for the FS info sector and
+        // a backup boot sector (mkfatfs/Microsoft's de facto default is 32);
+        // re-estimate against the now-larger reserved region.
+        if fat_type == FatType::Fat32 {
+            reserved_sectors = 32;
+            cluster_count = estimate_clusters(reserved_sectors);
+            fat_type = FatType::from_cluster_count(cluster_count);
+        }
+
+        let sectors_per_fat = Self::fat_size_sectors(cluster_count, fat_type);
+
+        Self {
+            num_blocks,
+            sectors_per_cluster,
+            reserved_sectors,
+            fat_type,
+            sectors_per_fat,
+            mbr: false,
+        }
+    }
+
+    /// Present the volume behind a single-partition MBR at LBA 0 instead of
+    /// the FAT boot sector directly, for hosts that refuse partition-less
+    /// media
+    pub fn with_mbr(mut self) -> Self {
+        self.mbr = true;
+        self
+    }
+
+    pub fn mbr_enabled(&self) -> bool {
+        self.mbr
+    }
+
+    /// LBA the FAT volume itself starts at: `0` normally, or `1` when an MBR
+    /// occupies LBA 0 ahead of it
+    pub(crate) fn partition_start(&self) -> u32 {
+        if self.mbr {
+            crate::mbr::PARTITION_START_LBA
+        } else {
+            0
+        }
+    }
+
+    fn fat_size_sectors(cluster_count: u32, fat_type: FatType) -> u32 {
+        // Clusters 0 and 1 are reserved and still need FAT entries
+        let entries = cluster_count + 2;
+        let bits = entries * fat_type.bits_per_entry();
+        let bytes = (bits + 7) / 8;
+        (bytes + BYTES_PER_SECTOR - 1) / BYTES_PER_SECTOR
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    pub fn sectors_per_fat(&self) -> u32 {
+        self.sectors_per_fat
+    }
+
+    pub fn start_fat0(&self) -> u32 {
+        self.reserved_sectors
+    }
+
+    pub fn start_rootdir(&self) -> u32 {
+        self.start_fat0() + NUM_FATS * self.sectors_per_fat
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // 8MB of storage, comfortably FAT16 sized to match the existing
+        // test expectations.
+        Self::new(16 * 1024, 1)
+    }
+}
@@ -0,0 +1,208 @@
+//! FAT directory entries, including VFAT long file name (LFN) entries
+
+use packing::{Packed, PackedSize};
+
+use crate::file::File;
+use crate::ASCII_SPACE;
+
+bitflags::bitflags! {
+    /// `DirectoryEntry::attrs` bits, per the FAT spec
+    #[derive(Default)]
+    pub struct FileAttributes: u8 {
+        const READ_ONLY = 0x01;
+        const HIDDEN = 0x02;
+        const SYSTEM = 0x04;
+        const VOLUME_ID = 0x08;
+        const DIRECTORY = 0x10;
+        const ARCHIVE = 0x20;
+    }
+}
+
+/// A single 32 byte FAT directory entry
+#[derive(Packed, PackedSize, Debug, Clone, Copy, Default)]
+#[packed(little_endian, lsb0)]
+pub struct DirectoryEntry {
+    #[pkd(7, 0, 0, 10)]
+    pub name: [u8; 11],
+    #[pkd(7, 0, 11, 11)]
+    pub attrs: u8,
+    #[pkd(7, 0, 12, 12)]
+    pub reserved: u8,
+    #[pkd(7, 0, 13, 13)]
+    pub create_time_tenth: u8,
+    #[pkd(7, 0, 14, 15)]
+    pub create_time: u16,
+    #[pkd(7, 0, 16, 17)]
+    pub create_date: u16,
+    #[pkd(7, 0, 18, 19)]
+    pub access_date: u16,
+    #[pkd(7, 0, 20, 21)]
+    pub start_cluster_hi: u16,
+    #[pkd(7, 0, 22, 23)]
+    pub write_time: u16,
+    #[pkd(7, 0, 24, 25)]
+    pub write_date: u16,
+    #[pkd(7, 0, 26, 27)]
+    pub start_cluster: u16,
+    #[pkd(7, 0, 28, 31)]
+    pub size: u32,
+}
+
+/// UCS-2 code units encoded by a single VFAT LFN entry
+pub const LFN_CHARS_PER_ENTRY: usize = 13;
+
+/// Attribute byte marking a directory entry as a VFAT LFN slot
+pub const LFN_ATTR: u8 = 0x0F;
+
+/// Sequence-number bit set on the slot holding the highest-ordinal name chunk
+const LFN_LAST_ENTRY: u8 = 0x40;
+
+/// A single VFAT long file name directory entry slot
+///
+/// A file whose name doesn't already match its own 8.3 short name gets a
+/// run of these immediately before its [`DirectoryEntry`], one per 13 UCS-2
+/// code units, laid out in descending sequence order.
+#[derive(Packed, PackedSize, Debug, Clone, Copy, Default)]
+#[packed(little_endian, lsb0)]
+pub struct LfnEntry {
+    #[pkd(7, 0, 0, 0)]
+    pub sequence: u8,
+    #[pkd(7, 0, 1, 10)]
+    pub name1: [u8; 10],
+    #[pkd(7, 0, 11, 11)]
+    pub attrs: u8,
+    #[pkd(7, 0, 12, 12)]
+    pub entry_type: u8,
+    #[pkd(7, 0, 13, 13)]
+    pub checksum: u8,
+    #[pkd(7, 0, 14, 25)]
+    pub name2: [u8; 12],
+    #[pkd(7, 0, 26, 27)]
+    pub start_cluster: u16,
+    #[pkd(7, 0, 28, 31)]
+    pub name3: [u8; 4],
+}
+
+impl LfnEntry {
+    /// Build the `seq`-th (1-based) LFN slot out of `total` for `name`
+    pub fn new(name: &str, seq: usize, total: usize, checksum: u8) -> Self {
+        let name_units: Vec<u16> = name.encode_utf16().collect();
+
+        let mut units = [0xFFFFu16; LFN_CHARS_PER_ENTRY];
+        let start = (seq - 1) * LFN_CHARS_PER_ENTRY;
+        let mut terminated = false;
+        for (i, unit) in units.iter_mut().enumerate() {
+            if let Some(&u) = name_units.get(start + i) {
+                *unit = u;
+            } else if !terminated {
+                *unit = 0x0000;
+                terminated = true;
+            }
+        }
+
+        let mut entry = Self {
+            sequence: seq as u8,
+            attrs: LFN_ATTR,
+            checksum,
+            ..Default::default()
+        };
+        if seq == total {
+            entry.sequence |= LFN_LAST_ENTRY;
+        }
+
+        pack_ucs2(&units[0..5], &mut entry.name1);
+        pack_ucs2(&units[5..11], &mut entry.name2);
+        pack_ucs2(&units[11..13], &mut entry.name3);
+
+        entry
+    }
+}
+
+fn pack_ucs2(units: &[u16], out: &mut [u8]) {
+    for (i, unit) in units.iter().enumerate() {
+        out[2 * i..2 * i + 2].copy_from_slice(&unit.to_le_bytes());
+    }
+}
+
+/// Checksum of an 8.3 short name, stored in each of its LFN entries so hosts
+/// can detect a short name that was modified without updating its LFN run
+pub fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name.iter().fold(0u8, |sum, &b| {
+        ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(b)
+    })
+}
+
+/// Directory-entry slots needed to list `children`: each contributes its LFN
+/// run (if any) plus its own 8.3 entry
+pub(crate) fn child_entries(children: &[File<'_>]) -> u32 {
+    children.iter().map(|f| f.lfn_entries() as u32 + 1).sum()
+}
+
+/// The 8.3 short name `children[index]` is served under, disambiguated from
+/// every earlier sibling via FAT's numeric-tail algorithm: a name that needs
+/// LFN, or a clean name that happens to collide with an earlier sibling's,
+/// gets a `~1`, `~2`, ... suffix instead (e.g. `config-1.txt` and
+/// `Config-1.txt` both fold to `CONFIG-1.TXT`, and are served as
+/// `CONFIG~1.TXT` and `CONFIG~2.TXT`)
+pub(crate) fn short_name_for(children: &[File<'_>], index: usize) -> [u8; 11] {
+    assigned_short_names(children)[index]
+}
+
+/// Assign every child in `children` its disambiguated short name, earlier
+/// siblings first, so each later name can check for collisions against what
+/// was already handed out
+fn assigned_short_names(children: &[File<'_>]) -> Vec<[u8; 11]> {
+    let mut assigned: Vec<[u8; 11]> = Vec::with_capacity(children.len());
+
+    for file in children {
+        let natural = file.name_fat16_short();
+
+        let name = if !file.needs_short_name_tail() && !assigned.contains(&natural) {
+            natural
+        } else {
+            (1..=assigned.len() as u32 + 1)
+                .map(|tail| numeric_tail_name(&natural, tail))
+                .find(|candidate| !assigned.contains(candidate))
+                // Unreachable: `assigned.len() + 1` distinct tails can't all
+                // collide with only `assigned.len()` existing names
+                .unwrap_or(natural)
+        };
+        assigned.push(name);
+    }
+
+    assigned
+}
+
+/// Render `natural`'s stem with a `~N` tail spliced in, e.g. `CONFIG~1` or
+/// `CONFI~12`, truncating the stem further to make room as `tail` grows
+fn numeric_tail_name(natural: &[u8; 11], tail: u32) -> [u8; 11] {
+    let mut tail_digits = [0u8; 10];
+    let mut n = tail;
+    let mut digit_count = 0;
+    loop {
+        tail_digits[digit_count] = b'0' + (n % 10) as u8;
+        n /= 10;
+        digit_count += 1;
+        if n == 0 {
+            break;
+        }
+    }
+
+    let stem_len = natural[..8].iter().position(|&b| b == ASCII_SPACE).unwrap_or(8);
+    let stem_len = stem_len.min(8usize.saturating_sub(digit_count + 1));
+
+    let mut short = [ASCII_SPACE; 11];
+    short[..stem_len].copy_from_slice(&natural[..stem_len]);
+    short[stem_len] = b'~';
+    for (i, &d) in tail_digits[..digit_count].iter().rev().enumerate() {
+        short[stem_len + 1 + i] = d;
+    }
+    short[8..11].copy_from_slice(&natural[8..11]);
+    short
+}
+
+/// Directory-entry slots needed for a non-root directory's own listing,
+/// including the `.` and `..` entries prepended to [`child_entries`]
+pub(crate) fn dir_entries_len(children: &[File<'_>]) -> u32 {
+    2 + child_entries(children)
+}
@@ -0,0 +1,228 @@
+//! Files registered with a [`crate::GhostFat`] instance
+
+use packing::PackedSize;
+
+use crate::dir::{self, DirectoryEntry, FileAttributes, LFN_CHARS_PER_ENTRY};
+use crate::time::{self, Timestamp};
+use crate::ASCII_SPACE;
+
+/// Errors raised when registering a [`File`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileError {
+    /// Name has no stem once split on `.` (e.g. `.`, `..` or an empty string)
+    EmptyName,
+}
+
+/// Backing storage for a [`File`]
+///
+/// Files created from an immutable slice are read-only; files created from
+/// a mutable slice accept host writes back into that buffer. A directory's
+/// "content" is its child entries rather than raw bytes -- the bytes of its
+/// directory-entry listing are generated on demand by [`crate::GhostFat`].
+pub enum FileContent<'a> {
+    ReadOnly(&'a [u8]),
+    ReadWrite(&'a mut [u8]),
+    Directory(&'a mut [File<'a>]),
+}
+
+impl<'a> From<&'a [u8]> for FileContent<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        FileContent::ReadOnly(data)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for FileContent<'a> {
+    fn from(data: &'a [u8; N]) -> Self {
+        FileContent::ReadOnly(data.as_slice())
+    }
+}
+
+impl<'a> From<&'a mut [u8]> for FileContent<'a> {
+    fn from(data: &'a mut [u8]) -> Self {
+        FileContent::ReadWrite(data)
+    }
+}
+
+impl<'a, const N: usize> From<&'a mut [u8; N]> for FileContent<'a> {
+    fn from(data: &'a mut [u8; N]) -> Self {
+        FileContent::ReadWrite(data.as_mut_slice())
+    }
+}
+
+/// A file exposed through the ghost FAT filesystem
+pub struct File<'a> {
+    name: &'a str,
+    content: FileContent<'a>,
+    /// Set to the registering [`crate::GhostFat`]'s current time once one
+    /// with a real [`crate::TimeProvider`] is built (`with_time_provider`);
+    /// the FAT epoch until then
+    created: Timestamp,
+    /// Bumped to the current time on every write that lands in this file's
+    /// data; otherwise equal to `created`
+    modified: Timestamp,
+}
+
+impl<'a> File<'a> {
+    pub fn new(name: &'a str, content: impl Into<FileContent<'a>>) -> Result<Self, FileError> {
+        Self::check_name(name)?;
+
+        Ok(Self {
+            name,
+            content: content.into(),
+            created: time::EPOCH,
+            modified: time::EPOCH,
+        })
+    }
+
+    /// Register a subdirectory, with `children` as its listing. A `.` and
+    /// `..` entry are synthesized for it automatically; `children` should
+    /// not include them.
+    pub fn new_dir(name: &'a str, children: &'a mut [File<'a>]) -> Result<Self, FileError> {
+        Self::check_name(name)?;
+
+        Ok(Self {
+            name,
+            content: FileContent::Directory(children),
+            created: time::EPOCH,
+            modified: time::EPOCH,
+        })
+    }
+
+    fn check_name(name: &str) -> Result<(), FileError> {
+        let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+        if stem.is_empty() || stem == "." {
+            return Err(FileError::EmptyName);
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.content {
+            FileContent::ReadOnly(d) => d.len(),
+            FileContent::ReadWrite(d) => d.len(),
+            FileContent::Directory(children) => dir::dir_entries_len(children) as usize * DirectoryEntry::BYTES,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.content, FileContent::Directory(_))
+    }
+
+    pub(crate) fn children(&self) -> Option<&[File<'a>]> {
+        match &self.content {
+            FileContent::Directory(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn children_mut(&mut self) -> Option<&mut [File<'a>]> {
+        match &mut self.content {
+            FileContent::Directory(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn created(&self) -> Timestamp {
+        self.created
+    }
+
+    pub(crate) fn modified(&self) -> Timestamp {
+        self.modified
+    }
+
+    pub(crate) fn set_created(&mut self, t: Timestamp) {
+        self.created = t;
+    }
+
+    pub(crate) fn set_modified(&mut self, t: Timestamp) {
+        self.modified = t;
+    }
+
+    pub fn data(&self) -> &[u8] {
+        match &self.content {
+            FileContent::ReadOnly(d) => d,
+            FileContent::ReadWrite(d) => d,
+            FileContent::Directory(_) => &[],
+        }
+    }
+
+    pub fn data_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.content {
+            FileContent::ReadOnly(_) => None,
+            FileContent::ReadWrite(d) => Some(d),
+            FileContent::Directory(_) => None,
+        }
+    }
+
+    pub fn attrs(&self) -> FileAttributes {
+        match &self.content {
+            FileContent::Directory(_) => FileAttributes::DIRECTORY,
+            FileContent::ReadOnly(_) => FileAttributes::ARCHIVE | FileAttributes::READ_ONLY,
+            FileContent::ReadWrite(_) => FileAttributes::ARCHIVE,
+        }
+    }
+
+    /// Render `self.name` as an 8.3 short name: uppercased, space padded and
+    /// truncated to fit, with no regard for whether it collides with a
+    /// sibling's. Long or mixed-case names are only losslessly recoverable
+    /// from the LFN entries [`File::lfn_entries`] asks for; a truncated name
+    /// that collides with a sibling's is disambiguated by
+    /// [`dir::short_name_for`](crate::dir::short_name_for) instead, since
+    /// that requires seeing the rest of the directory.
+    pub fn name_fat16_short(&self) -> [u8; 11] {
+        let mut short = [ASCII_SPACE; 11];
+
+        let (stem, ext) = match self.name.rsplit_once('.') {
+            Some((stem, ext)) => (stem, ext),
+            None => (self.name, ""),
+        };
+
+        for (i, c) in stem.chars().take(8).enumerate() {
+            short[i] = c.to_ascii_uppercase() as u8;
+        }
+        for (i, c) in ext.chars().take(3).enumerate() {
+            short[8 + i] = c.to_ascii_uppercase() as u8;
+        }
+
+        short
+    }
+
+    /// Whether [`File::name_fat16_short`] had to fold case or truncate this
+    /// name to fit 8.3, and so always needs a numeric tail regardless of
+    /// whether it happens to collide with a sibling's (a clean name only
+    /// needs one if it collides, checked separately by the caller)
+    pub(crate) fn needs_short_name_tail(&self) -> bool {
+        !Self::is_clean_short_name(self.name)
+    }
+
+    /// Number of VFAT LFN slots this file's name needs: zero when the short
+    /// 8.3 name already losslessly represents it
+    pub(crate) fn lfn_entries(&self) -> usize {
+        if Self::is_clean_short_name(self.name) {
+            0
+        } else {
+            let units = self.name.encode_utf16().count();
+            (units + LFN_CHARS_PER_ENTRY - 1) / LFN_CHARS_PER_ENTRY
+        }
+    }
+
+    fn is_clean_short_name(name: &str) -> bool {
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) => (stem, ext),
+            None => (name, ""),
+        };
+
+        !stem.is_empty()
+            && stem.len() <= 8
+            && ext.len() <= 3
+            && name.chars().all(|c| c.is_ascii() && !c.is_ascii_lowercase())
+    }
+}
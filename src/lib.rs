@@ -15,35 +15,122 @@ use usbd_scsi::{BlockDevice, BlockDeviceError};
 
 pub mod config;
 pub use config::Config;
+use config::FatType;
 
 pub mod boot;
 use boot::FatBootBlock;
 
 pub mod dir;
-use dir::DirectoryEntry;
+use dir::{DirectoryEntry, FileAttributes};
 
 pub mod file;
 use file::{File};
 
+pub mod uf2;
+use uf2::{FlashTarget, NoFlashTarget, Uf2Block, Uf2State};
+
+pub mod time;
+use time::{DefaultTimeProvider, TimeProvider};
+
+pub mod mbr;
+use mbr::MbrPartitionEntry;
+
 const UF2_SIZE: u32 = 0x10000 * 2;
 const UF2_SECTORS: u32 = UF2_SIZE / (512 as u32);
 
-const ASCII_SPACE: u8 = 0x20;
+pub(crate) const ASCII_SPACE: u8 = 0x20;
+
+const MEDIA_DESCRIPTOR: u8 = 0xF8;
+
+/// FAT end-of-chain marker; each FAT width only keeps its low bits
+const FAT_EOC: u32 = 0x0FFF_FFFF;
+
+/// Number of 512 byte blocks needed to hold `len` bytes
+fn blocks_for(len: usize) -> usize {
+    (len + GhostFat::BLOCK_BYTES - 1) / GhostFat::BLOCK_BYTES
+}
+
+/// Total root directory entry slots: the volume label, plus each file's LFN
+/// run and its 8.3 entry. Shared by [`GhostFat::new`] (to size the boot
+/// block's `RootEntCnt`) and [`GhostFat::root_dir_entries`] (to size the
+/// root directory region itself) so the two can never disagree.
+fn root_dir_entry_count(files: &[File]) -> u32 {
+    1 + files.iter().map(|f| f.lfn_entries() as u32 + 1).sum::<u32>()
+}
 
 
 /// # Dummy fat implementation that provides a [UF2 bootloader](https://github.com/microsoft/uf2)
-pub struct GhostFat<'a> {
+pub struct GhostFat<'a, T: FlashTarget = NoFlashTarget, P: TimeProvider = DefaultTimeProvider> {
     config: Config,
     fat_boot_block: FatBootBlock,
     pub(crate) fat_files: &'a mut [File<'a>],
+    flash_target: Option<T>,
+    uf2_state: Uf2State,
+    time_provider: Option<P>,
+}
+
+/// Which node a data-region cluster belongs to, found by [`GhostFat::locate_cluster`]
+struct ClusterLoc<'f, 'a> {
+    node: &'f File<'a>,
+    /// 0-based block offset within `node`'s own data/listing
+    block_in_node: usize,
+    /// Blocks `node`'s own data/listing occupies
+    own_blocks: usize,
+    /// `node`'s own first cluster, meaningful when `node` is a directory
+    self_cluster: u32,
+    /// Cluster `node`'s parent directory starts at (0 for the root), used
+    /// for a directory's `..` entry
+    parent_cluster: u32,
 }
 
-impl <'a> GhostFat<'a> {
+impl <'a, T: FlashTarget, P: TimeProvider> GhostFat<'a, T, P> {
     pub fn new(files: &'a mut [File<'a>], config: Config) -> Self {
+        let root_dir_entries = root_dir_entry_count(files);
         Self {
-            fat_boot_block: FatBootBlock::new(&config),
+            fat_boot_block: FatBootBlock::new(&config, root_dir_entries),
             fat_files: files,
             config,
+            flash_target: None,
+            uf2_state: Uf2State::default(),
+            time_provider: None,
+        }
+    }
+
+    /// Flash UF2 writes landing in the UF2 cluster range to `target`
+    pub fn with_flash_target(mut self, target: T) -> Self {
+        self.flash_target = Some(target);
+        self
+    }
+
+    /// Stamp every registered file's creation time with `provider`'s current
+    /// time instead of leaving it at the FAT epoch. Each file's modified
+    /// time starts out equal to its creation time and only moves from there
+    /// when a write lands in it.
+    pub fn with_time_provider(mut self, provider: P) -> Self {
+        self.time_provider = Some(provider);
+        let now = self.current_time();
+        Self::stamp_registration_time(self.fat_files, now);
+        self
+    }
+
+    /// Recursively stamp `files` (and every child of a directory among them)
+    /// as created and modified `now`, once at registration time
+    fn stamp_registration_time(files: &mut [File<'a>], now: time::Timestamp) {
+        for file in files.iter_mut() {
+            file.set_created(now);
+            file.set_modified(now);
+            if let Some(children) = file.children_mut() {
+                Self::stamp_registration_time(children, now);
+            }
+        }
+    }
+
+    /// Current time to stamp a directory entry with, falling back to the
+    /// FAT epoch when no [`TimeProvider`] was registered
+    fn current_time(&self) -> time::Timestamp {
+        match &self.time_provider {
+            Some(p) => p.now(),
+            None => DefaultTimeProvider.now(),
         }
     }
 
@@ -51,245 +138,605 @@ impl <'a> GhostFat<'a> {
         let lba = addr / 512;
         let _offset = addr % 512;
 
-      
+
         Ok(())
     }
 
-}
+    /// Allocation value for FAT entry (cluster) `entry`
+    ///
+    /// Entries 0 and 1 are the reserved media-descriptor and EOC entries.
+    /// From cluster 2 onward, clusters are handed out to each registered
+    /// file or directory (and, recursively, its children) in turn, followed
+    /// by the UF2 region; everything past that is free (0).
+    fn fat_entry_value(&self, entry: u32) -> u32 {
+        match entry {
+            0 => 0xFFFF_FF00 | MEDIA_DESCRIPTOR as u32,
+            1 => FAT_EOC,
+            _ => match self.locate_cluster((entry - 2) as usize) {
+                Some(loc) => {
+                    if loc.block_in_node + 1 == loc.own_blocks {
+                        FAT_EOC
+                    } else {
+                        entry + 1
+                    }
+                }
+                None => {
+                    // The UF2 ingestion region follows the static files and directories
+                    let uf2_first_cluster = self.first_uf2_cluster();
+                    let uf2_last_cluster = uf2_first_cluster + UF2_SECTORS - 1;
+                    if entry >= uf2_first_cluster && entry < uf2_last_cluster {
+                        entry + 1
+                    } else if entry == uf2_last_cluster {
+                        FAT_EOC
+                    } else {
+                        0
+                    }
+                }
+            },
+        }
+    }
 
-impl <'a>BlockDevice for GhostFat<'a> {
-    const BLOCK_BYTES: usize = 512;
+    /// Locate the node (and, if it's a directory, the dot-entry clusters) that
+    /// the data-region cluster `cluster_index` (0-based, counting from the
+    /// first data cluster) belongs to
+    fn locate_cluster(&self, cluster_index: usize) -> Option<ClusterLoc<'_, 'a>> {
+        let mut next_cluster = 2u32;
+        Self::locate_in(self.fat_files, 0, &mut next_cluster, cluster_index)
+    }
 
-    fn read_block(&self, lba: u32, block: &mut [u8]) -> Result<(), BlockDeviceError> {
-        assert_eq!(block.len(), Self::BLOCK_BYTES);
+    /// Recursive step of [`GhostFat::locate_cluster`]: pre-order walk of
+    /// `files`, descending into each directory's children immediately after
+    /// its own clusters so clusters stay contiguous per subtree
+    fn locate_in<'f>(
+        files: &'f [File<'a>],
+        parent_cluster: u32,
+        next_cluster: &mut u32,
+        mut target: usize,
+    ) -> Option<ClusterLoc<'f, 'a>> {
+        for node in files {
+            let node_cluster = *next_cluster;
+            let own_blocks = blocks_for(node.len());
+
+            if target < own_blocks {
+                return Some(ClusterLoc {
+                    node,
+                    block_in_node: target,
+                    own_blocks,
+                    self_cluster: node_cluster,
+                    parent_cluster,
+                });
+            }
+            target -= own_blocks;
+            *next_cluster += own_blocks as u32;
+
+            if let Some(children) = node.children() {
+                let before = *next_cluster;
+                if let Some(found) = Self::locate_in(children, node_cluster, next_cluster, target) {
+                    return Some(found);
+                }
+                target -= (*next_cluster - before) as usize;
+            }
+        }
+        None
+    }
+
+    /// The byte at `byte_offset` within the (single) on-disk FAT, given its width
+    fn fat_entry_byte(&self, fat_type: FatType, byte_offset: u64) -> u8 {
+        match fat_type {
+            FatType::Fat12 => {
+                let pair = byte_offset / 3;
+                let entry_lo = self.fat_entry_value(2 * pair as u32);
+                match byte_offset % 3 {
+                    0 => (entry_lo & 0xFF) as u8,
+                    1 => {
+                        let entry_hi = self.fat_entry_value(2 * pair as u32 + 1);
+                        (((entry_lo >> 8) & 0x0F) | ((entry_hi & 0x0F) << 4)) as u8
+                    }
+                    _ => {
+                        let entry_hi = self.fat_entry_value(2 * pair as u32 + 1);
+                        ((entry_hi >> 4) & 0xFF) as u8
+                    }
+                }
+            }
+            FatType::Fat16 => {
+                let entry = self.fat_entry_value((byte_offset / 2) as u32);
+                if byte_offset % 2 == 0 {
+                    (entry & 0xFF) as u8
+                } else {
+                    ((entry >> 8) & 0xFF) as u8
+                }
+            }
+            FatType::Fat32 => {
+                // FAT32 entries are only 28 bits wide; the top nibble of the
+                // high byte is reserved and must read back as zero, even for
+                // the all-ones reserved/EOC markers `fat_entry_value` shares
+                // with FAT12/FAT16.
+                let entry = self.fat_entry_value((byte_offset / 4) as u32) & 0x0FFF_FFFF;
+                ((entry >> (8 * (byte_offset % 4))) & 0xFF) as u8
+            }
+        }
+    }
 
-        debug!("GhostFAT reading lba: {} ({} bytes)", lba, block.len());
+    /// Total root directory entry slots: the volume label, plus each file's
+    /// LFN run and its 8.3 entry
+    fn root_dir_entries(&self) -> u32 {
+        root_dir_entry_count(self.fat_files)
+    }
 
-        // Clear the buffer since we're sending all of it
-        for b in block.iter_mut() {
-            *b = 0
+    /// Sectors occupied by the root directory; zero on FAT32, where it lives
+    /// in the cluster region like any other directory
+    fn root_dir_sectors(&self) -> u32 {
+        match self.config.fat_type() {
+            FatType::Fat32 => 0,
+            _ => {
+                let bytes = self.root_dir_entries() * DirectoryEntry::BYTES as u32;
+                (bytes + Self::BLOCK_BYTES as u32 - 1) / Self::BLOCK_BYTES as u32
+            }
         }
+    }
 
-        // Block 0 is the fat boot block
-        if lba == 0 {
-            self.fat_boot_block
-                .pack(&mut block[..FatBootBlock::BYTES])
-                .unwrap();
-            block[510] = 0x55;
-            block[511] = 0xAA;
+    /// First cluster-region LBA, accounting for the LFN-driven root directory size
+    fn start_clusters(&self) -> u32 {
+        self.config.start_rootdir() + self.root_dir_sectors()
+    }
 
-        // File allocation table(s) follow the boot block
-        } else if lba < self.config.start_rootdir() {
-            let mut section_index = lba - self.config.start_fat0();
+    /// Write the root directory entry at absolute `entry_index` into `out`
+    ///
+    /// Entry 0 is the volume label; each registered file or directory then
+    /// contributes its LFN run (highest sequence number first) followed by
+    /// its 8.3 entry. Anything past the last entry is left zeroed (a free slot).
+    fn root_dir_entry(&self, entry_index: u32, out: &mut [u8]) {
+        if entry_index == 0 {
+            let mut label = DirectoryEntry::default();
+            label.name.copy_from_slice(&self.fat_boot_block.volume_label);
+            label.attrs = (FileAttributes::VOLUME_ID | FileAttributes::ARCHIVE).bits();
+            let now = self.current_time();
+            Self::stamp_entry(&mut label, now, now);
+            label.pack(out).unwrap();
+            return;
+        }
 
-            // TODO: why?
-            // https://github.com/lupyuen/bluepill-bootloader/blob/master/src/ghostfat.c#L207
-            if section_index >= self.config.sectors_per_fat() {
-                section_index -= self.config.sectors_per_fat();
+        Self::children_entry(self.fat_files, 1, 2, entry_index, out);
+    }
+
+    /// Write the directory entry (or LFN slot) at absolute `entry_index`
+    /// among `children`'s combined LFN+8.3 run into `out`, walking from
+    /// `base_index`/`base_cluster` (the slot and cluster the first child
+    /// starts at). Shared by the root directory (after its volume-label
+    /// slot) and [`GhostFat::dir_entry`] (after a subdirectory's `.`/`..`).
+    fn children_entry(children: &[File<'a>], base_index: u32, base_cluster: u32, entry_index: u32, out: &mut [u8]) {
+        let mut index = base_index;
+        let mut cluster_index = base_cluster;
+
+        for (child_index, info) in children.iter().enumerate() {
+            let lfn_count = info.lfn_entries() as u32;
+            let short_name = dir::short_name_for(children, child_index);
+
+            if entry_index < index + lfn_count {
+                let slot_from_end = entry_index - index;
+                let seq = (lfn_count - slot_from_end) as usize;
+                let checksum = dir::short_name_checksum(&short_name);
+                let lfn = dir::LfnEntry::new(info.name(), seq, lfn_count as usize, checksum);
+                lfn.pack(out).unwrap();
+                return;
             }
 
-            // Track block indicies for each file
-            let mut index = 1;
+            if entry_index == index + lfn_count {
+                let mut entry = DirectoryEntry::default();
+                entry.start_cluster = cluster_index as u16;
+                entry.start_cluster_hi = (cluster_index >> 16) as u16;
+                entry.name.copy_from_slice(&short_name);
+                entry.size = if info.is_dir() { 0 } else { info.len() as u32 };
+                entry.attrs = info.attrs().bits();
+                Self::stamp_entry(&mut entry, info.created(), info.modified());
+                entry.pack(out).unwrap();
+                return;
+            }
 
-            // Set allocations for static files
-            if section_index == 0 {
-                block[0] = 0xF0;
+            index += lfn_count + 1;
+            cluster_index += Self::subtree_clusters(info);
+        }
+    }
 
-                for f in self.fat_files.iter() {
-                    // Determine number of blocks required for each file
-                    let mut block_count = f.len() / Self::BLOCK_BYTES;
-                    if f.len() % Self::BLOCK_BYTES != 0 {
-                        block_count += 1;
-                    }
+    /// Write directory entry `entry_index` of a subdirectory's own listing
+    /// (`.` at 0, `..` at 1, then [`GhostFat::children_entry`]) into `out`.
+    /// `created`/`modified` are the directory's own (`loc.node`'s) timestamps,
+    /// reused for both dot entries.
+    fn dir_entry(
+        children: &[File<'a>],
+        self_cluster: u32,
+        parent_cluster: u32,
+        entry_index: u32,
+        created: time::Timestamp,
+        modified: time::Timestamp,
+        out: &mut [u8],
+    ) {
+        if entry_index == 0 {
+            Self::dot_entry(b".          ", self_cluster, created, modified).pack(out).unwrap();
+            return;
+        }
+        if entry_index == 1 {
+            Self::dot_entry(b"..         ", parent_cluster, created, modified).pack(out).unwrap();
+            return;
+        }
 
-                    // Write block allocations (2 byte)
-                    for i in 0..block_count {
-                        if i == block_count - 1 {
-                            // Final block containes 0xFFFF
-                            block[index + i] = 0xFF;
-                            block[index + i + 1] = 0xFF;
-                        } else {
-                            // Preceding blocks should link to next object
-                            // TODO: not sure this linking is correct... should split and test
-                            block[index + i] = ((index + i + 2) >> 8) as u8;
-                            block[index + i + 1] =  (index + i + 3) as u8;
-                        }
-                    }
+        let own_blocks = blocks_for(dir::dir_entries_len(children) as usize * DirectoryEntry::BYTES) as u32;
+        Self::children_entry(children, 2, self_cluster + own_blocks, entry_index, out);
+    }
 
-                    // Increase block index
-                    index += block_count * 2;
-                }
+    fn dot_entry(name: &[u8; 11], cluster: u32, created: time::Timestamp, modified: time::Timestamp) -> DirectoryEntry {
+        let mut entry = DirectoryEntry::default();
+        entry.name.copy_from_slice(name);
+        entry.attrs = FileAttributes::DIRECTORY.bits();
+        entry.start_cluster = cluster as u16;
+        entry.start_cluster_hi = (cluster >> 16) as u16;
+        Self::stamp_entry(&mut entry, created, modified);
+        entry
+    }
 
-                // Write trailer
-                for i in 0..4 {
-                    block[index + i] = 0xFF;
-                }
-                index += 4;
+    /// Stamp `entry`'s create/write/access fields from a file's persisted
+    /// timestamps. FAT has no access *time*, only a date, so `access_date`
+    /// just mirrors `modified`'s date.
+    fn stamp_entry(entry: &mut DirectoryEntry, created: time::Timestamp, modified: time::Timestamp) {
+        entry.create_time_tenth = created.tenths;
+        entry.create_time = created.fat_time();
+        entry.create_date = created.fat_date();
+        entry.write_time = modified.fat_time();
+        entry.write_date = modified.fat_date();
+        entry.access_date = modified.fat_date();
+    }
+
+    /// Clusters consumed by `node`: its own data/listing, plus (recursively)
+    /// everything under it if it's a directory
+    fn subtree_clusters(node: &File<'a>) -> u32 {
+        blocks_for(node.len()) as u32
+            + node
+                .children()
+                .map(|c| c.iter().map(Self::subtree_clusters).sum())
+                .unwrap_or(0)
+    }
+
+    /// First cluster of the UF2 ingestion region, immediately after the
+    /// static files' (and directories' subtrees') clusters
+    fn first_uf2_cluster(&self) -> u32 {
+        2 + self.fat_files.iter().map(Self::subtree_clusters).sum::<u32>()
+    }
 
+    /// Parse `block` as a UF2 block and, unless it's flagged `noflash`, hand
+    /// its payload to the registered [`FlashTarget`]; finalize once every
+    /// block of the transfer has arrived
+    fn handle_uf2_write(&mut self, block: &[u8]) {
+        let uf2_block = match Uf2Block::parse(block) {
+            Some(b) => b,
+            None => {
+                warn!("Ignoring non-UF2 write to UF2 region");
+                return;
             }
+        };
+
+        if uf2_block.has_family_id() {
+            debug!("UF2 block for family 0x{:08x}", uf2_block.file_size_or_family_id);
+        }
 
-            // Set remaining sectors as occupied
-            for b in &mut block[index..] {
-                *b = 0xFF;
+        if let Some(target) = self.flash_target.as_mut() {
+            if uf2_block.block_no == 0 {
+                let len = uf2_block.payload_size.saturating_mul(uf2_block.num_blocks.max(1));
+                if target.erase(uf2_block.target_addr, len).is_err() {
+                    error!("Flash erase failed at 0x{:08x}", uf2_block.target_addr);
+                }
             }
 
-            // TODO: is this setting allocations for the uf2 file?
-            // WTH is happening here and why is it load bearing..?
-
-            // Assuming each file is one block, uf2 is offset by this
-            let uf2_first_sector = self.fat_files.len() + 1;
-            let uf2_last_sector = uf2_first_sector + UF2_SECTORS as usize - 1;
-
-            for i in 0..256_usize {
-                let v = section_index as usize * 256 + i;
-                let j = 2 * i;
-                if v >= uf2_first_sector && v < uf2_last_sector {
-                    block[j + 0] = (((v + 1) >> 0) & 0xFF) as u8;
-                    block[j + 1] = (((v + 1) >> 8) & 0xFF) as u8;
-                } else if v == uf2_last_sector {
-                    block[j + 0] = 0xFF;
-                    block[j + 1] = 0xFF;
+            if !uf2_block.is_noflash() {
+                if target.write(uf2_block.target_addr, uf2_block.data).is_err() {
+                    error!("Flash write failed at 0x{:08x}", uf2_block.target_addr);
                 }
             }
+        }
 
+        if self.uf2_state.record(uf2_block.block_no, uf2_block.num_blocks) {
+            info!("UF2 transfer complete, finalizing flash target");
+            if let Some(target) = self.flash_target.as_mut() {
+                if target.finalize().is_err() {
+                    error!("Flash finalize failed");
+                }
+            }
+        }
+    }
+}
 
-        // Directory entries follow
-        } else if lba < self.config.start_clusters() {
-            let section_index = lba - self.config.start_rootdir();
-            if section_index == 0 {
-                let mut dir = DirectoryEntry::default();
-                dir.name.copy_from_slice(&self.fat_boot_block.volume_label);
-                dir.attrs = 0x28;
+impl<'a, T: FlashTarget, P: TimeProvider> GhostFat<'a, T, P> {
+    fn read_boot_block(&self, block: &mut [u8; 512]) {
+        self.fat_boot_block
+            .pack(&mut block[..FatBootBlock::BYTES])
+            .unwrap();
+        block[510] = 0x55;
+        block[511] = 0xAA;
+    }
 
-                let len = DirectoryEntry::BYTES;
-                dir.pack(&mut block[..len]).unwrap();
-                dir.attrs = 0;
+    /// Build the MBR sector served at LBA 0 when [`Config::with_mbr`] is set,
+    /// with a single partition entry pointing at the FAT boot block
+    fn read_mbr_block(&self, block: &mut [u8; 512]) {
+        const PARTITION_TABLE_OFFSET: usize = 446;
 
-                // Starting cluster index (after BBL and FAT)
-                let mut cluster_index = 2;
+        MbrPartitionEntry::new(&self.config)
+            .pack(&mut block[PARTITION_TABLE_OFFSET..PARTITION_TABLE_OFFSET + MbrPartitionEntry::BYTES])
+            .unwrap();
+        block[510] = 0x55;
+        block[511] = 0xAA;
+    }
 
-                // Generate directory entries for registered files
-                for (i, info) in self.fat_files.iter().enumerate() {
-                    // Determine number of blocks required for each file
-                    let mut block_count = info.len() / Self::BLOCK_BYTES;
-                    if info.len() % Self::BLOCK_BYTES != 0 {
-                        block_count += 1;
-                    }
-                    dir.start_cluster = cluster_index as u16;
-                    
-                    // Write attributes
-                    dir.name.copy_from_slice(&info.name_fat16_short().unwrap());
-                    dir.size = info.len() as u32;
-                    dir.attrs = info.attrs().bits();
-
-                    // Encode to block
-                    let start = (i + 1) * len;
-                    dir.pack(&mut block[start..(start + len)]).unwrap();
-
-                    // Increment cluster index
-                    cluster_index += block_count;
-                }
+    /// Serve a sector in the FAT32 reserved region between the boot sector
+    /// and the FAT ([`Config::start_fat0`]'s 32 sectors, vs. FAT12/FAT16's
+    /// single boot sector): the FSInfo sector and a verbatim copy of the
+    /// boot sector at the LBAs [`FatBootBlock::new`] advertises for them,
+    /// zeroed padding everywhere else (already done by `fill_blocks`).
+    fn read_reserved_block(&self, lba: u32, block: &mut [u8; 512]) {
+        match self.config.fat_type() {
+            FatType::Fat32 if lba == self.fat_boot_block.fs_info_sector as u32 => {
+                self.read_fs_info_block(block);
             }
+            FatType::Fat32 if lba == self.fat_boot_block.backup_boot_sector as u32 => {
+                self.read_boot_block(block);
+            }
+            _ => {}
+        }
+    }
 
-        // Then finally clusters (containing actual data)
-        } else {
-            let section_index = (lba - self.config.start_clusters()) as usize;
-
-            // Iterate through files to find matching block
-            let mut block_index = 0;
-            for f in self.fat_files.iter() {
+    /// FAT32's FSInfo sector: advisory free-cluster bookkeeping hosts may
+    /// use as a hint. GhostFAT doesn't track free clusters, so both counts
+    /// are left at the spec's "unknown" value.
+    fn read_fs_info_block(&self, block: &mut [u8; 512]) {
+        const LEAD_SIG: u32 = 0x4161_5252;
+        const STRUCT_SIG: u32 = 0x6141_7272;
+        const TRAIL_SIG: u32 = 0xAA55_0000;
+        const UNKNOWN: u32 = 0xFFFF_FFFF;
+
+        block[0..4].copy_from_slice(&LEAD_SIG.to_le_bytes());
+        block[484..488].copy_from_slice(&STRUCT_SIG.to_le_bytes());
+        block[488..492].copy_from_slice(&UNKNOWN.to_le_bytes());
+        block[492..496].copy_from_slice(&UNKNOWN.to_le_bytes());
+        block[508..512].copy_from_slice(&TRAIL_SIG.to_le_bytes());
+    }
 
-                // Determine number of blocks required for each file
-                let mut block_count = f.len() / Self::BLOCK_BYTES;
-                if f.len() % Self::BLOCK_BYTES != 0 {
-                    block_count += 1;
-                }
+    fn read_fat_block(&self, lba: u32, block: &mut [u8; 512]) {
+        let section_index = lba - self.config.start_fat0();
+        let fat_type = self.config.fat_type();
 
-                // If the LBA is within the file, return data
-                if section_index < block_count + block_index {
-                    let offset = section_index - block_index;
+        // Each output byte is a pure function of its absolute offset into
+        // the FAT, so sector-straddling FAT12 entries fall out for free
+        // without carrying state between reads.
+        for (i, b) in block.iter_mut().enumerate() {
+            let byte_offset = section_index as u64 * Self::BLOCK_BYTES as u64 + i as u64;
+            *b = self.fat_entry_byte(fat_type, byte_offset);
+        }
+    }
 
-                    if let Some(chunk) = f.data().chunks(512).nth(offset) {
-                        block[..chunk.len()].copy_from_slice(chunk);
-                    }
+    fn read_dir_block(&self, lba: u32, block: &mut [u8; 512]) {
+        let section_index = lba - self.config.start_rootdir();
+        let entries_per_sector = (Self::BLOCK_BYTES / DirectoryEntry::BYTES) as u32;
 
-                    return Ok(())
-                }
+        for slot in 0..entries_per_sector {
+            let entry_index = section_index * entries_per_sector + slot;
+            let start = slot as usize * DirectoryEntry::BYTES;
+            self.root_dir_entry(entry_index, &mut block[start..start + DirectoryEntry::BYTES]);
+        }
+    }
 
-                // Otherwise, continue
-                block_index += block_count;
+    /// Fill as many of `blocks` as possible from a single contiguous run in
+    /// the cluster region, returning how many were filled (always at least 1)
+    ///
+    /// Locates the owning node once via [`GhostFat::locate_cluster`], then
+    /// either streams a file's data straight into the output blocks with
+    /// `chunks(512)` or, for a directory, generates its entry listing one
+    /// sector at a time.
+    fn read_cluster_run(&self, start_lba: u32, blocks: &mut [[u8; 512]]) -> usize {
+        let cluster_index = (start_lba - self.start_clusters()) as usize;
+
+        let loc = match self.locate_cluster(cluster_index) {
+            Some(loc) => loc,
+            None => {
+                // Not part of any registered file or directory (UF2 region, or past the end)
+                debug!("Unhandled read section: {}", cluster_index);
+                return 1;
             }
+        };
 
-            debug!("Unhandled read section: {}", section_index);
+        match loc.node.children() {
+            None => {
+                let take = blocks.len().min(loc.own_blocks - loc.block_in_node);
+                let mut filled = 0;
+                for (out, chunk) in blocks[..take]
+                    .iter_mut()
+                    .zip(loc.node.data()[loc.block_in_node * 512..].chunks(512))
+                {
+                    out[..chunk.len()].copy_from_slice(chunk);
+                    filled += 1;
+                }
+                filled.max(1)
+            }
+            Some(children) => {
+                let entries_per_sector = (Self::BLOCK_BYTES / DirectoryEntry::BYTES) as u32;
+                let sector = loc.block_in_node as u32;
+                for slot in 0..entries_per_sector {
+                    let entry_index = sector * entries_per_sector + slot;
+                    let start = slot as usize * DirectoryEntry::BYTES;
+                    Self::dir_entry(
+                        children,
+                        loc.self_cluster,
+                        loc.parent_cluster,
+                        entry_index,
+                        loc.node.created(),
+                        loc.node.modified(),
+                        &mut blocks[0][start..start + DirectoryEntry::BYTES],
+                    );
+                }
+                1
+            }
         }
-        Ok(())
     }
 
-    fn write_block(&mut self, lba: u32, block: &[u8]) -> Result<(), BlockDeviceError> {
-        debug!("GhostFAT writing lba: {} ({} bytes)", lba, block.len());
+    fn fill_blocks(&self, start_lba: u32, blocks: &mut [[u8; 512]]) -> Result<(), BlockDeviceError> {
+        debug!("GhostFAT reading lba: {} ({} blocks)", start_lba, blocks.len());
 
-        if lba == 0 {
-            warn!("Attempted write to boot sector");
-            return Ok(());
-
-        // Write to FAT
-        } else if lba < self.config.start_rootdir() {
-            // TODO: should we support this?
-            warn!("Attempted to write to FAT");
-
-        // Write directory entry
-        } else if lba < self.config.start_clusters() {
-            // TODO: do we need to wrap this somehow to remap writes?
-            warn!("Attempted to write directory entries");
+        // Zero every requested block up front: `read_cluster_run` can fill
+        // several blocks in one call, and the tail block of a file's last
+        // run only copies in `chunk.len()` bytes, leaving the rest to
+        // whatever was already in the caller's buffer if we don't.
+        for block in blocks.iter_mut() {
+            block.iter_mut().for_each(|b| *b = 0);
+        }
 
-            let section_index = lba - self.config.start_rootdir();
-            if section_index == 0 {
+        let partition_start = self.config.partition_start();
 
+        let mut i = 0;
+        while i < blocks.len() {
+            let lba = start_lba + i as u32;
 
+            if lba < partition_start {
+                self.read_mbr_block(&mut blocks[i]);
+                i += 1;
+                continue;
             }
+            let lba = lba - partition_start;
+
+            if lba == 0 {
+                self.read_boot_block(&mut blocks[i]);
+                i += 1;
+            } else if lba < self.config.start_fat0() {
+                self.read_reserved_block(lba, &mut blocks[i]);
+                i += 1;
+            } else if lba < self.config.start_rootdir() {
+                self.read_fat_block(lba, &mut blocks[i]);
+                i += 1;
+            } else if lba < self.start_clusters() {
+                self.read_dir_block(lba, &mut blocks[i]);
+                i += 1;
+            } else {
+                i += self.read_cluster_run(lba, &mut blocks[i..]);
+            }
+        }
 
-        // Write cluster data
-        } else {
-            let section_index = (lba - self.config.start_clusters()) as usize;
-
-            // Iterate through files to find matching block
-            let mut block_index = 0;
-            for f in self.fat_files.iter_mut() {
+        Ok(())
+    }
 
-                // Determine number of blocks required for each file
-                let mut block_count = f.len() / Self::BLOCK_BYTES;
-                if f.len() % Self::BLOCK_BYTES != 0 {
-                    block_count += 1;
-                }
+    /// Handle a single cluster-region write: either host data into a
+    /// registered `File`, or a UF2 payload into the flash region
+    fn write_cluster_block(&mut self, lba: u32, block: &[u8; 512]) {
+        let cluster_index = (lba - self.start_clusters()) as usize;
+        let now = self.current_time();
 
-                // If the LBA is within the file, write data
-                if section_index < block_count + block_index {
-                    let offset = section_index - block_index;
+        if Self::write_in(self.fat_files, cluster_index, block, now) {
+            return;
+        }
 
-                    debug!("Write file: {} block: {}, {} bytes", f.name(), offset, block.len());
+        // Past the static files and directories: this is the UF2 ingestion region
+        if cluster_index as u32 + 2 >= self.first_uf2_cluster() {
+            self.handle_uf2_write(block);
+        } else {
+            warn!("Unhandled write section: {}", cluster_index);
+        }
+    }
 
-                    if let Some(chunk) = f.data_mut().map(|d| d.chunks_mut(512).nth(offset) ).flatten() {
+    /// Recursive step of [`GhostFat::write_cluster_block`]: find the node
+    /// `target` (a data-region cluster offset, relative to `files`' own
+    /// first cluster) belongs to and write `block` into it; returns `false`
+    /// if `target` falls outside `files` and its subtrees entirely
+    fn write_in(files: &mut [File<'a>], mut target: usize, block: &[u8; 512], now: time::Timestamp) -> bool {
+        for node in files.iter_mut() {
+            let own_blocks = blocks_for(node.len());
+
+            if target < own_blocks {
+                if node.is_dir() {
+                    warn!("Attempted to write to directory entries");
+                } else {
+                    debug!("Write file: {} block: {}, {} bytes", node.name(), target, block.len());
+
+                    if let Some(chunk) = node.data_mut().map(|d| d.chunks_mut(512).nth(target)).flatten() {
                         let max_len = usize::min(block.len(), chunk.len());
-                        chunk[..max_len].copy_from_slice(&block[..max_len])
+                        chunk[..max_len].copy_from_slice(&block[..max_len]);
+                        node.set_modified(now);
                     } else {
                         error!("Attempted to write to read-only file");
                     }
-
-                    return Ok(())
                 }
+                return true;
+            }
+            target -= own_blocks;
 
-                // Otherwise, continue
-                block_index += block_count;
+            if let Some(child_span) = node.children().map(|c| c.iter().map(Self::subtree_clusters).sum::<u32>() as usize) {
+                if target < child_span {
+                    return Self::write_in(node.children_mut().unwrap(), target, block, now);
+                }
+                target -= child_span;
             }
+        }
+        false
+    }
+
+    fn drain_blocks(&mut self, start_lba: u32, blocks: &[[u8; 512]]) -> Result<(), BlockDeviceError> {
+        debug!("GhostFAT writing lba: {} ({} blocks)", start_lba, blocks.len());
+
+        let partition_start = self.config.partition_start();
+
+        for (i, block) in blocks.iter().enumerate() {
+            let lba = start_lba + i as u32;
 
-            warn!("Unhandled write section: {}", section_index);
+            if lba < partition_start {
+                warn!("Attempted write to MBR");
+                continue;
+            }
+            let lba = lba - partition_start;
+
+            if lba == 0 {
+                warn!("Attempted write to boot sector");
+            } else if lba < self.config.start_rootdir() {
+                // TODO: should we support this?
+                warn!("Attempted to write to FAT");
+            } else if lba < self.start_clusters() {
+                // TODO: do we need to wrap this somehow to remap writes?
+                warn!("Attempted to write directory entries");
+            } else {
+                self.write_cluster_block(lba, block);
+            }
         }
 
         Ok(())
     }
+}
+
+impl <'a, T: FlashTarget, P: TimeProvider>BlockDevice for GhostFat<'a, T, P> {
+    const BLOCK_BYTES: usize = 512;
+
+    fn read_block(&self, lba: u32, block: &mut [u8]) -> Result<(), BlockDeviceError> {
+        assert_eq!(block.len(), Self::BLOCK_BYTES);
+
+        let mut single = [0u8; 512];
+        self.fill_blocks(lba, core::slice::from_mut(&mut single))?;
+        block.copy_from_slice(&single);
+
+        Ok(())
+    }
+
+    /// Read a run of sequential blocks in one pass; see [`GhostFat::read_cluster_run`]
+    /// for the hot path this saves (a contiguous large-file read).
+    fn read_blocks(&self, start_lba: u32, blocks: &mut [[u8; 512]]) -> Result<(), BlockDeviceError> {
+        self.fill_blocks(start_lba, blocks)
+    }
+
+    fn write_block(&mut self, lba: u32, block: &[u8]) -> Result<(), BlockDeviceError> {
+        assert_eq!(block.len(), Self::BLOCK_BYTES);
+
+        let mut single = [0u8; 512];
+        single.copy_from_slice(block);
+        self.drain_blocks(lba, core::slice::from_ref(&single))
+    }
+
+    fn write_blocks(&mut self, start_lba: u32, blocks: &[[u8; 512]]) -> Result<(), BlockDeviceError> {
+        self.drain_blocks(start_lba, blocks)
+    }
 
     fn max_lba(&self) -> u32 {
-        self.config.num_blocks - 1
+        self.config.num_blocks - 1 + self.config.partition_start()
     }
 }
 
@@ -303,17 +750,19 @@ mod tests {
 
     use fatfs::{FsOptions, FatType};
     use usbd_scsi::BlockDevice;
+    use packing::{Packed, PackedSize};
 
     use crate::{GhostFat, File, config::Config};
+    use crate::uf2::{FlashTarget, NoFlashTarget};
+    use crate::time::{self, TimeProvider, DefaultTimeProvider};
+    use crate::dir::DirectoryEntry;
 
-    pub struct MockDisk<'a> {
+    pub struct MockDisk<'a, T: FlashTarget = NoFlashTarget, P: TimeProvider = DefaultTimeProvider> {
         pub index: usize,
-        pub disk: GhostFat<'a>,
+        pub disk: GhostFat<'a, T, P>,
     }
 
-    // TODO: read/write do not yet handle multiple blocks
-
-    impl <'a> Read for MockDisk<'a> {
+    impl <'a, T: FlashTarget, P: TimeProvider> Read for MockDisk<'a, T, P> {
         fn read(&mut self, buff: &mut [u8]) -> std::io::Result<usize> {
             // Map block to index and buff len
             let mut lba = self.index as u32 / 512;
@@ -360,7 +809,7 @@ mod tests {
         }
     }
 
-    impl <'a> Write for MockDisk<'a> {
+    impl <'a, T: FlashTarget, P: TimeProvider> Write for MockDisk<'a, T, P> {
         fn write(&mut self, buff: &[u8]) -> std::io::Result<usize> {
 
             // Map block to index and buff len
@@ -398,7 +847,7 @@ mod tests {
         }
     }
 
-    impl <'a> Seek for MockDisk<'a> {
+    impl <'a, T: FlashTarget, P: TimeProvider> Seek for MockDisk<'a, T, P> {
         fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
             // Handle seek mechanisms
             match pos {
@@ -638,4 +1087,260 @@ mod tests {
         f1.read_to_string(&mut s0).unwrap();
         assert_eq!(s0.as_bytes(), d2);
     }
+
+    #[test]
+    fn root_dir_grows_past_fixed_sixteen_entries() {
+        // 20 files (21 entries with the volume label) overflows the old
+        // hardcoded ROOT_DIR_ENTRIES of 16; every one must still round-trip.
+        let mut names = Vec::new();
+        for i in 0..20 {
+            names.push(format!("FILE{:04}.TXT", i));
+        }
+        let data = b"abc123456";
+        let mut files: Vec<File> = names.iter().map(|n| File::new(n.as_str(), data.as_ref()).unwrap()).collect();
+
+        let disk = setup(&mut files);
+
+        let fs = fatfs::FileSystem::new(disk, FsOptions::new()).unwrap();
+        let root_dir = fs.root_dir();
+
+        let f: Vec<_> = root_dir.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(f.len(), names.len());
+
+        for (name, entry) in names.iter().zip(f.iter()) {
+            assert_eq!(&entry.short_file_name(), name);
+            let mut file = entry.to_file();
+            let mut s = String::new();
+            file.read_to_string(&mut s).unwrap();
+            assert_eq!(s.as_bytes(), data);
+        }
+    }
+
+    #[test]
+    fn fat32_reserves_room_for_fs_info_and_backup_boot_sector() {
+        // Large enough that cluster_count crosses the FAT16 ceiling (65525).
+        let config = Config::new(70_000, 1);
+
+        assert_eq!(config.fat_type(), crate::config::FatType::Fat32);
+        assert_eq!(config.reserved_sectors, 32);
+
+        // The backup boot sector lives at reserved sector 6; the FAT itself
+        // must start after it, not swallow it.
+        assert!(config.start_fat0() > 6);
+    }
+
+    #[test]
+    fn colliding_short_names_get_distinct_numeric_tails() {
+        // Both names are over 8 characters, so both need a short-name tail
+        // and would otherwise truncate to the same "LONGNAME.TXT".
+        let files = &mut [
+            File::new("LONGNAME1.TXT", b"one".as_ref()).unwrap(),
+            File::new("LONGNAME2.TXT", b"two".as_ref()).unwrap(),
+        ];
+
+        let disk = setup(files);
+
+        let fs = fatfs::FileSystem::new(disk, FsOptions::new()).unwrap();
+        let root_dir = fs.root_dir();
+
+        let f: Vec<_> = root_dir.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(f.len(), 2);
+        assert_ne!(f[0].short_file_name(), f[1].short_file_name());
+    }
+
+    #[test]
+    fn batched_read_zeros_tail_of_last_block() {
+        // Spans two blocks, with the second only partially filled by file
+        // data -- the rest must read back as zero, not leftover buffer junk.
+        let mut data = [0u8; 600];
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_add(1);
+        }
+        let files = &mut [File::new("TEST.BIN", data.as_ref()).unwrap()];
+
+        let ghost_fat: GhostFat<'_> = GhostFat::new(files, Config::default());
+        let start_lba = ghost_fat.start_clusters();
+
+        let mut blocks = [[0xAAu8; 512]; 2];
+        ghost_fat.read_blocks(start_lba, &mut blocks).unwrap();
+
+        assert_eq!(&blocks[0][..], &data[..512]);
+        assert_eq!(&blocks[1][..88], &data[512..]);
+        assert!(blocks[1][88..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn uf2_duplicate_block_does_not_double_count() {
+        let mut state = crate::uf2::Uf2State::default();
+
+        assert!(!state.record(0, 3));
+        assert!(!state.record(0, 3)); // retried/duplicate write of the same block
+        assert!(!state.record(1, 3));
+        assert!(state.record(2, 3));
+    }
+
+    #[test]
+    fn uf2_huge_num_blocks_does_not_trigger_unbounded_allocation() {
+        let mut state = crate::uf2::Uf2State::default();
+
+        // A malformed/corrupted write claiming a near-u32::MAX block count
+        // must not be taken at face value -- it's clamped before anything
+        // gets sized off of it.
+        assert!(!state.record(0, u32::MAX));
+    }
+
+    #[test]
+    fn fat32_reserved_region_reads_without_underflow() {
+        let files = &mut [File::new("BIG.TXT", b"hello fat32 world".as_ref()).unwrap()];
+
+        let config = Config::new(70_000, 1);
+        assert_eq!(config.fat_type(), crate::config::FatType::Fat32);
+
+        let ghost_fat: GhostFat<'_> = GhostFat::new(files, config);
+
+        // FSInfo sector, advertised at LBA 1
+        let mut fs_info = [0u8; 512];
+        ghost_fat.read_block(1, &mut fs_info).unwrap();
+        assert_eq!(&fs_info[0..4], &0x4161_5252u32.to_le_bytes());
+        assert_eq!(&fs_info[508..512], &0xAA55_0000u32.to_le_bytes());
+
+        // Backup boot sector, advertised at LBA 6, mirrors the primary one
+        let mut boot = [0u8; 512];
+        ghost_fat.read_block(0, &mut boot).unwrap();
+        let mut backup = [0u8; 512];
+        ghost_fat.read_block(6, &mut backup).unwrap();
+        assert_eq!(boot, backup);
+
+        // The rest of the reserved region is just padding
+        let mut padding = [0xAAu8; 512];
+        ghost_fat.read_block(2, &mut padding).unwrap();
+        assert!(padding.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn fat32_reserved_fat_entries_mask_top_nibble() {
+        let files = &mut [File::new("BIG.TXT", b"hello fat32 world".as_ref()).unwrap()];
+
+        let config = Config::new(70_000, 1);
+        let ghost_fat: GhostFat<'_> = GhostFat::new(files, config);
+
+        // Entry 0's high byte is the all-ones reserved/media-descriptor
+        // marker shared with FAT12/FAT16, but FAT32 entries are only 28
+        // bits wide -- the top nibble must read back as zero.
+        assert_eq!(ghost_fat.fat_entry_byte(crate::config::FatType::Fat32, 3), 0x0F);
+    }
+
+    /// Stubbed [`TimeProvider`] for [`with_time_provider_stamps_directory_entry`]
+    #[derive(Debug, Clone, Copy)]
+    struct FixedTimeProvider(time::Timestamp);
+
+    impl TimeProvider for FixedTimeProvider {
+        fn now(&self) -> time::Timestamp {
+            self.0
+        }
+    }
+
+    #[test]
+    fn with_time_provider_stamps_directory_entry() {
+        let files = &mut [File::new("STAMP.TXT", b"hello time provider".as_ref()).unwrap()];
+
+        let stamped = time::Timestamp {
+            year: 2024,
+            month: 3,
+            day: 14,
+            hour: 9,
+            minute: 26,
+            second: 53,
+            tenths: 7,
+        };
+
+        let ghost_fat: GhostFat<'_, NoFlashTarget, FixedTimeProvider> =
+            GhostFat::new(files, Config::default()).with_time_provider(FixedTimeProvider(stamped));
+
+        // Root dir entry 0 is the volume label; entry 1 is STAMP.TXT's 8.3
+        // entry (its name fits 8.3, so it needs no LFN run ahead of it).
+        let mut block = [0u8; 512];
+        ghost_fat.read_block(ghost_fat.config.start_rootdir(), &mut block).unwrap();
+
+        let entry = DirectoryEntry::unpack(&block[DirectoryEntry::BYTES..2 * DirectoryEntry::BYTES]).unwrap();
+        assert_eq!(&entry.name, b"STAMP   TXT");
+        assert_eq!(entry.create_time_tenth, stamped.tenths);
+        assert_eq!(entry.create_time, stamped.fat_time());
+        assert_eq!(entry.create_date, stamped.fat_date());
+        assert_eq!(entry.write_time, stamped.fat_time());
+        assert_eq!(entry.write_date, stamped.fat_date());
+        assert_eq!(entry.access_date, stamped.fat_date());
+    }
+
+    /// Presents only the FAT volume behind a [`MockDisk`] built over a
+    /// [`Config::with_mbr`] volume, shifting every seek by one sector so
+    /// `fatfs::FileSystem::new` can mount the partition without having to
+    /// parse the MBR itself
+    struct PartitionDisk<'a> {
+        disk: MockDisk<'a>,
+        partition_offset: u64,
+    }
+
+    impl <'a> Read for PartitionDisk<'a> {
+        fn read(&mut self, buff: &mut [u8]) -> std::io::Result<usize> {
+            self.disk.read(buff)
+        }
+    }
+
+    impl <'a> Write for PartitionDisk<'a> {
+        fn write(&mut self, buff: &[u8]) -> std::io::Result<usize> {
+            self.disk.write(buff)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.disk.flush()
+        }
+    }
+
+    impl <'a> Seek for PartitionDisk<'a> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            let pos = match pos {
+                SeekFrom::Start(v) => SeekFrom::Start(self.partition_offset + v),
+                other => other,
+            };
+            Ok(self.disk.seek(pos)? - self.partition_offset)
+        }
+    }
+
+    #[test]
+    fn config_with_mbr_round_trips_through_fatfs() {
+        let data = b"hello partitioned world";
+        let files = &mut [File::new("MBR.TXT", data.as_ref()).unwrap()];
+
+        let config = Config::default().with_mbr();
+        let ghost_fat = GhostFat::new(files, config);
+
+        // LBA 0 is the MBR itself: a single partition entry covering the FAT
+        // volume that starts right after it, per `MbrPartitionEntry::new`.
+        const PARTITION_TABLE_OFFSET: usize = 446;
+        let mut mbr = [0u8; 512];
+        ghost_fat.read_block(0, &mut mbr).unwrap();
+        assert_eq!(&mbr[510..512], &[0x55, 0xAA]);
+        let partition = crate::mbr::MbrPartitionEntry::unpack(
+            &mbr[PARTITION_TABLE_OFFSET..PARTITION_TABLE_OFFSET + crate::mbr::MbrPartitionEntry::BYTES],
+        )
+        .unwrap();
+        assert_eq!(partition.partition_type, 0x0E); // FAT16-or-smaller, LBA-addressed
+        assert_eq!(partition.start_lba, crate::mbr::PARTITION_START_LBA);
+        assert_eq!(partition.num_sectors, config.num_blocks);
+
+        let disk = MockDisk { index: 0, disk: ghost_fat };
+        let partitioned = PartitionDisk { disk, partition_offset: 512 };
+
+        let fs = fatfs::FileSystem::new(partitioned, FsOptions::new()).unwrap();
+        assert_eq!(fs.fat_type(), FatType::Fat16);
+
+        let root_dir = fs.root_dir();
+        let f: Vec<_> = root_dir.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(f[0].short_file_name(), "MBR.TXT");
+
+        let mut s0 = String::new();
+        f[0].to_file().read_to_string(&mut s0).unwrap();
+        assert_eq!(s0.as_bytes(), data);
+    }
 }
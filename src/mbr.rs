@@ -0,0 +1,56 @@
+//! Optional Master Boot Record, presenting the FAT volume as a single
+//! partition for hosts that refuse partition-less media
+
+use packing::{Packed, PackedSize};
+
+use crate::config::{Config, FatType};
+
+/// LBA the partition starts at: the MBR itself occupies LBA 0
+pub(crate) const PARTITION_START_LBA: u32 = 1;
+
+/// Partition type byte for a FAT16 (or smaller) volume addressed via LBA
+const PARTITION_TYPE_FAT16_LBA: u8 = 0x0E;
+/// Partition type byte for a FAT32 volume addressed via LBA
+const PARTITION_TYPE_FAT32_LBA: u8 = 0x0C;
+
+/// CHS fields aren't modeled; maxing them out tells CHS-only readers to fall
+/// back to the LBA fields instead
+const CHS_UNUSED: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+/// A single 16 byte MBR partition table entry
+#[derive(Packed, PackedSize, Debug, Clone, Copy, Default)]
+#[packed(little_endian, lsb0)]
+pub struct MbrPartitionEntry {
+    #[pkd(7, 0, 0, 0)]
+    pub status: u8,
+    #[pkd(7, 0, 1, 3)]
+    pub chs_start: [u8; 3],
+    #[pkd(7, 0, 4, 4)]
+    pub partition_type: u8,
+    #[pkd(7, 0, 5, 7)]
+    pub chs_end: [u8; 3],
+    #[pkd(7, 0, 8, 11)]
+    pub start_lba: u32,
+    #[pkd(7, 0, 12, 15)]
+    pub num_sectors: u32,
+}
+
+impl MbrPartitionEntry {
+    /// The single partition entry covering the whole FAT volume, starting
+    /// right after this MBR sector
+    pub fn new(config: &Config) -> Self {
+        let partition_type = match config.fat_type() {
+            FatType::Fat32 => PARTITION_TYPE_FAT32_LBA,
+            FatType::Fat12 | FatType::Fat16 => PARTITION_TYPE_FAT16_LBA,
+        };
+
+        Self {
+            status: 0,
+            chs_start: CHS_UNUSED,
+            partition_type,
+            chs_end: CHS_UNUSED,
+            start_lba: PARTITION_START_LBA,
+            num_sectors: config.num_blocks,
+        }
+    }
+}
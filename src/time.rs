@@ -0,0 +1,64 @@
+//! Pluggable time source for directory-entry timestamps
+
+/// A calendar timestamp, already split into the fields FAT's packed
+/// date/time format is built from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Full year, e.g. `2024` (FAT can only represent 1980..=2107)
+    pub year: u16,
+    /// 1-12
+    pub month: u8,
+    /// 1-31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Tenths of a second past `second`, FAT's only sub-second resolution,
+    /// and only stored for a creation time
+    pub tenths: u8,
+}
+
+impl Timestamp {
+    /// FAT packed date: `((year-1980) << 9) | (month << 5) | day`
+    pub fn fat_date(&self) -> u16 {
+        (self.year.saturating_sub(1980) << 9) | ((self.month as u16) << 5) | self.day as u16
+    }
+
+    /// FAT packed time: `(hour << 11) | (minute << 5) | (second / 2)`
+    pub fn fat_time(&self) -> u16 {
+        ((self.hour as u16) << 11) | ((self.minute as u16) << 5) | (self.second as u16 / 2)
+    }
+}
+
+/// The earliest timestamp FAT can represent. What a [`crate::File`] is
+/// created with before any [`crate::GhostFat`] has stamped it with a real
+/// one, and what [`DefaultTimeProvider`] always reports.
+pub const EPOCH: Timestamp = Timestamp {
+    year: 1980,
+    month: 1,
+    day: 1,
+    hour: 0,
+    minute: 0,
+    second: 0,
+    tenths: 0,
+};
+
+/// Source of wall-clock time for directory-entry timestamps
+pub trait TimeProvider {
+    /// The current time. Queried when files are registered (to stamp their
+    /// creation time), when a write lands in a file (to bump its modified
+    /// time) and when the volume label entry is built; other directory
+    /// entries carry their file's stored timestamps
+    fn now(&self) -> Timestamp;
+}
+
+/// No-op [`TimeProvider`] for hosts without an RTC: every timestamp reads as
+/// the FAT epoch (1980-01-01 00:00:00)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultTimeProvider;
+
+impl TimeProvider for DefaultTimeProvider {
+    fn now(&self) -> Timestamp {
+        EPOCH
+    }
+}
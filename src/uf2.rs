@@ -0,0 +1,154 @@
+//! UF2 (USB Flashing Format) block parsing and flash ingestion
+//!
+//! See the [UF2 spec](https://github.com/microsoft/uf2) for the wire format.
+
+/// First UF2 start magic, stored at block offset 0
+const UF2_MAGIC_START0: u32 = 0x0A32_4655;
+/// Second UF2 start magic, stored at block offset 4
+const UF2_MAGIC_START1: u32 = 0x9E5D_5157;
+/// UF2 end magic, stored at block offset 476
+const UF2_MAGIC_END: u32 = 0x0AB1_6F30;
+
+const UF2_MAGIC_END_OFFSET: usize = 476;
+const UF2_PAYLOAD_OFFSET: usize = 32;
+
+/// `flags` bit: this block is informational only and must not be flashed
+pub const UF2_FLAG_NOFLASH: u32 = 0x0000_0001;
+/// `flags` bit: `file_size_or_family_id` holds a family ID rather than a file size
+pub const UF2_FLAG_FAMILY_ID_PRESENT: u32 = 0x0000_2000;
+
+/// A single parsed UF2 block, borrowed from the 512 byte write it arrived in
+#[derive(Debug, Clone, Copy)]
+pub struct Uf2Block<'a> {
+    pub flags: u32,
+    pub target_addr: u32,
+    pub payload_size: u32,
+    pub block_no: u32,
+    pub num_blocks: u32,
+    pub file_size_or_family_id: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Uf2Block<'a> {
+    /// Parse a 512 byte block, returning `None` if its magics don't match
+    pub fn parse(block: &'a [u8]) -> Option<Self> {
+        let read_u32 =
+            |offset: usize| block.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+
+        if read_u32(0)? != UF2_MAGIC_START0 || read_u32(4)? != UF2_MAGIC_START1 {
+            return None;
+        }
+        if read_u32(UF2_MAGIC_END_OFFSET)? != UF2_MAGIC_END {
+            return None;
+        }
+
+        let flags = read_u32(8)?;
+        let target_addr = read_u32(12)?;
+        let block_no = read_u32(20)?;
+        let num_blocks = read_u32(24)?;
+        let file_size_or_family_id = read_u32(28)?;
+
+        // Clamp to the space actually available between the header and the
+        // trailing magic, in case a malformed block claims more
+        let max_payload = (UF2_MAGIC_END_OFFSET - UF2_PAYLOAD_OFFSET) as u32;
+        let payload_size = read_u32(16)?.min(max_payload);
+        let data = &block[UF2_PAYLOAD_OFFSET..UF2_PAYLOAD_OFFSET + payload_size as usize];
+
+        Some(Self {
+            flags,
+            target_addr,
+            payload_size,
+            block_no,
+            num_blocks,
+            file_size_or_family_id,
+            data,
+        })
+    }
+
+    pub fn is_noflash(&self) -> bool {
+        self.flags & UF2_FLAG_NOFLASH != 0
+    }
+
+    pub fn has_family_id(&self) -> bool {
+        self.flags & UF2_FLAG_FAMILY_ID_PRESENT != 0
+    }
+}
+
+/// Flash device a [`crate::GhostFat`] writes ingested UF2 payloads into
+pub trait FlashTarget {
+    type Error;
+
+    /// Write `data` at `addr`
+    fn write(&mut self, addr: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Erase `len` bytes starting at `addr`, ahead of the writes that will follow
+    fn erase(&mut self, addr: u32, len: u32) -> Result<(), Self::Error>;
+
+    /// Called once every block of a UF2 transfer has been received
+    fn finalize(&mut self) -> Result<(), Self::Error>;
+}
+
+/// No-op [`FlashTarget`] for hosts that expose the UF2 cluster region without
+/// actually flashing anything
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoFlashTarget;
+
+impl FlashTarget for NoFlashTarget {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, _addr: u32, _data: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn erase(&mut self, _addr: u32, _len: u32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Hard ceiling on a UF2 transfer's declared block count. `num_blocks` comes
+/// straight off the wire (block offset 24) with no validation from the host,
+/// and [`Uf2State::record`] allocates a bitmap sized to it -- without a
+/// ceiling, a single malformed or corrupted write claiming a `num_blocks`
+/// near `u32::MAX` would trigger a half-gigabyte allocation attempt, a real
+/// crash risk on the memory-constrained embedded targets this crate runs on.
+/// 1M blocks is comfortably above any real firmware image (256MB at UF2's
+/// 256 byte payload size).
+const MAX_UF2_BLOCKS: u32 = 1 << 20;
+
+/// Tracks which blocks of the in-progress UF2 transfer have arrived, keyed by
+/// `block_no` rather than a bare count -- a retried or duplicate write of the
+/// same block (not unusual over USB MSC) must not be double-counted towards
+/// completion
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Uf2State {
+    total_blocks: u32,
+    /// One bit per block index, 64 indices to a word
+    received: Vec<u64>,
+}
+
+impl Uf2State {
+    /// Record a block's arrival, returning `true` once every block in
+    /// `0..num_blocks` has been received at least once
+    pub(crate) fn record(&mut self, block_no: u32, num_blocks: u32) -> bool {
+        let num_blocks = num_blocks.min(MAX_UF2_BLOCKS);
+
+        if num_blocks != self.total_blocks {
+            // A transfer with a different block count started; reset
+            self.total_blocks = num_blocks;
+            self.received.clear();
+            self.received.resize((num_blocks as usize + 63) / 64, 0);
+        }
+
+        if block_no < self.total_blocks {
+            let (word, bit) = (block_no as usize / 64, block_no as usize % 64);
+            self.received[word] |= 1 << bit;
+        }
+
+        self.total_blocks > 0
+            && self.received.iter().map(|word| word.count_ones()).sum::<u32>() >= self.total_blocks
+    }
+}